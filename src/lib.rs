@@ -11,36 +11,198 @@ use tauri::{
     Manager, Runtime, Window,
 };
 
+/// Allow/forbid rules for command names, modeled on Tauri's `FsScope`.
+///
+/// A command is permitted when it matches at least one allow pattern (an empty
+/// allow list permits everything, preserving the plugin's original behavior)
+/// and matches no forbid pattern. The forbid list always takes precedence.
+/// Patterns are exact names or globs using `*` as a wildcard.
+#[derive(Default, Clone)]
+pub struct CommandScope {
+    allowed: Vec<String>,
+    forbidden: Vec<String>,
+}
+
+impl CommandScope {
+    /// Returns whether `command` is permitted by this scope.
+    pub fn is_allowed(&self, command: &str) -> bool {
+        if self.forbidden.iter().any(|p| glob_match(p, command)) {
+            return false;
+        }
+        self.allowed.is_empty() || self.allowed.iter().any(|p| glob_match(p, command))
+    }
+}
+
+/// Match `pattern` against `value`, treating `*` as a wildcard for any run of
+/// characters. Any other character matches literally.
+fn glob_match(pattern: &str, value: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let v: Vec<char> = value.chars().collect();
+
+    // Iterative backtracking matcher (no regex dependency).
+    let (mut pi, mut vi) = (0usize, 0usize);
+    let (mut star, mut mark) = (None, 0usize);
+    while vi < v.len() {
+        if pi < p.len() && (p[pi] == '*') {
+            star = Some(pi);
+            mark = vi;
+            pi += 1;
+        } else if pi < p.len() && p[pi] == v[vi] {
+            pi += 1;
+            vi += 1;
+        } else if let Some(s) = star {
+            pi = s + 1;
+            mark += 1;
+            vi = mark;
+        } else {
+            return false;
+        }
+    }
+    while pi < p.len() && p[pi] == '*' {
+        pi += 1;
+    }
+    pi == p.len()
+}
+
 /// Shared state for automation
 pub struct AutomationState {
     pub window: Arc<Mutex<Option<Window<tauri::Wry>>>>,
+    /// Allow/forbid rules applied to incoming command requests.
+    pub scope: CommandScope,
+    /// Expected bearer token; when set, requests must present it to run.
+    pub token: Option<String>,
 }
 
 impl Default for AutomationState {
     fn default() -> Self {
         Self {
             window: Arc::new(Mutex::new(None)),
+            scope: CommandScope::default(),
+            token: None,
         }
     }
 }
 
-/// Initialize the automation plugin
-pub fn init() -> TauriPlugin<tauri::Wry> {
-    Builder::new("automation")
-        .setup(|app| {
-            let state = AutomationState::default();
-            app.manage(state);
-
-            // Start HTTP server in background thread
-            let app_handle = app.clone();
-            std::thread::spawn(move || {
-                server::start_server(app_handle);
-            });
-
-            println!("[Automation] Plugin initialized - HTTP server starting on port 9876");
-            Ok(())
-        })
-        .build()
+/// Builder for the automation plugin.
+///
+/// Use [`init`] to obtain a builder, configure the command scope and optional
+/// token, then call [`AutomationBuilder::build`] to get the Tauri plugin:
+///
+/// ```ignore
+/// tauri::Builder::default()
+///     .plugin(
+///         tauri_plugin_automation::init()
+///             .allow("app.*")
+///             .forbid("app.danger")
+///             .token("s3cret")
+///             .build(),
+///     )
+/// ```
+#[derive(Default)]
+pub struct AutomationBuilder {
+    scope: CommandScope,
+    token: Option<String>,
+    host: Option<String>,
+    port: Option<u16>,
+    tls: Option<server::TlsConfig>,
+}
+
+impl AutomationBuilder {
+    /// Create a builder with an empty scope and no token.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind the server to `host` instead of `127.0.0.1`.
+    pub fn host<S: Into<String>>(mut self, host: S) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    /// Bind the server to `port` instead of the default `9876`.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Serve over TLS using the given PEM certificate chain and private key,
+    /// switching the listener from `http` to `https`.
+    pub fn tls(mut self, certificate: Vec<u8>, private_key: Vec<u8>) -> Self {
+        self.tls = Some(server::TlsConfig {
+            certificate,
+            private_key,
+        });
+        self
+    }
+
+    /// Permit commands matching `pattern` (exact name or `*` glob).
+    pub fn allow<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.scope.allowed.push(pattern.into());
+        self
+    }
+
+    /// Forbid commands matching `pattern`; forbids always take precedence.
+    pub fn forbid<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.scope.forbidden.push(pattern.into());
+        self
+    }
+
+    /// Require clients to present this token via `Authorization: Bearer`.
+    pub fn token<S: Into<String>>(mut self, token: S) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Build the configured plugin.
+    pub fn build(self) -> TauriPlugin<tauri::Wry> {
+        let scope = self.scope;
+        let token = self.token;
+        let config = server::ServerConfig {
+            host: self.host.unwrap_or_else(|| "127.0.0.1".to_string()),
+            port: self.port.unwrap_or(9876),
+            tls: self.tls,
+        };
+        // Record the port and the internal webview secret before injecting the
+        // shim so its callbacks target the right port and carry the secret.
+        server::set_active_port(config.port);
+        server::set_internal_token(server::random_token());
+        Builder::new("automation")
+            // Install the console-forwarding shim into every webview so its
+            // output can be streamed over the `/automation/ws` channel.
+            .js_init_script(server::console_shim_js())
+            .setup(move |app| {
+                let state = AutomationState {
+                    scope,
+                    token,
+                    ..Default::default()
+                };
+                app.manage(state);
+
+                // Forward app-emitted automation events onto the WebSocket stream.
+                app.listen_global("automation:event", |event| {
+                    let payload = event
+                        .payload()
+                        .and_then(|p| serde_json::from_str::<serde_json::Value>(p).ok())
+                        .unwrap_or(serde_json::Value::Null);
+                    server::broadcast(serde_json::json!({ "type": "event", "payload": payload }));
+                });
+
+                // Start HTTP server in background thread
+                let app_handle = app.clone();
+                std::thread::spawn(move || {
+                    server::start_server(app_handle, config);
+                });
+
+                tracing::info!("automation plugin initialized; HTTP server starting");
+                Ok(())
+            })
+            .build()
+    }
+}
+
+/// Initialize the automation plugin, returning a builder to configure it.
+pub fn init() -> AutomationBuilder {
+    AutomationBuilder::new()
 }
 
 /// Execute an automation command via JavaScript evaluation
@@ -1,86 +1,556 @@
 //! HTTP server for automation commands
 
+use std::collections::HashMap;
 use std::io::Read;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicU16, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
 use tauri::{AppHandle, Manager};
-use tiny_http::{Server, Response, Header, Method};
+use tiny_http::{Server, Response, Header, Method, ReadWrite};
+use tungstenite::{protocol::Role, Message, WebSocket};
 
-const PORT: u16 = 9876;
+/// Default bind address and port when the builder leaves them unset.
+const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 9876;
 
-// Global screenshot buffer (simple approach without lazy_static)
-static SCREENSHOT_DATA: Mutex<Option<String>> = Mutex::new(None);
+/// Bind configuration for the automation HTTP(S) server.
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    pub tls: Option<TlsConfig>,
+}
 
-pub fn set_screenshot_data(data: String) {
-    if let Ok(mut guard) = SCREENSHOT_DATA.lock() {
-        *guard = Some(data);
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: DEFAULT_HOST.to_string(),
+            port: DEFAULT_PORT,
+            tls: None,
+        }
     }
 }
 
-pub fn take_screenshot_data() -> Option<String> {
-    if let Ok(mut guard) = SCREENSHOT_DATA.lock() {
-        guard.take()
-    } else {
-        None
+/// PEM-encoded certificate chain and private key for TLS, loaded via rustls.
+pub struct TlsConfig {
+    pub certificate: Vec<u8>,
+    pub private_key: Vec<u8>,
+}
+
+// The port the server actually bound, shared so request handlers and the
+// injected webview scripts can address the server on the configured port.
+static ACTIVE_PORT: AtomicU16 = AtomicU16::new(DEFAULT_PORT);
+
+/// Record the active port; call before injecting webview scripts that must
+/// reach the server.
+pub fn set_active_port(port: u16) {
+    ACTIVE_PORT.store(port, Ordering::Relaxed);
+}
+
+// Secret shared only with the webview (injected at load) so the internal
+// `_result`/`_event` callbacks can be distinguished from frames forged by
+// another process on the loopback interface.
+static INTERNAL_TOKEN: Mutex<Option<String>> = Mutex::new(None);
+
+/// Generate an unguessable random token (used for the internal webview secret).
+pub fn random_token() -> String {
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(REQUEST_SEQ.fetch_add(1, Ordering::Relaxed));
+    let hi = hasher.finish();
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(hi);
+    format!("{:016x}{:016x}", hi, hasher.finish())
+}
+
+/// Record the internal webview token; call before injecting webview scripts.
+pub fn set_internal_token(token: String) {
+    if let Ok(mut guard) = INTERNAL_TOKEN.lock() {
+        *guard = Some(token);
     }
 }
 
-/// Start the HTTP server
-pub fn start_server(app_handle: AppHandle<tauri::Wry>) {
-    let addr = format!("127.0.0.1:{}", PORT);
+/// The secret the webview must echo on `_result`/`_event` posts.
+fn internal_token() -> String {
+    INTERNAL_TOKEN
+        .lock()
+        .ok()
+        .and_then(|g| g.clone())
+        .unwrap_or_default()
+}
 
-    let server = match Server::http(&addr) {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("[Automation] Failed to start server on {}: {}", addr, e);
-            return;
+/// The port the automation server is reachable on.
+fn active_port() -> u16 {
+    ACTIVE_PORT.load(Ordering::Relaxed)
+}
+
+/// Upper bounds (milliseconds) for the latency histograms' cumulative buckets.
+const LATENCY_BUCKETS_MS: [u64; 8] = [1, 5, 10, 25, 50, 100, 250, 500];
+
+/// A cumulative histogram plus sum/count, recorded with atomics so the whole
+/// recorder can live in a `static` without `lazy_static`.
+struct Histogram {
+    buckets: [std::sync::atomic::AtomicU64; 8],
+    sum_ms: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; 8],
+            sum_ms: AtomicU64::new(0),
+            count: AtomicU64::new(0),
         }
-    };
+    }
 
-    println!("[Automation] HTTP server listening on http://{}", addr);
+    fn observe(&self, ms: u64) {
+        for (i, le) in LATENCY_BUCKETS_MS.iter().enumerate() {
+            if ms <= *le {
+                self.buckets[i].fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.sum_ms.fetch_add(ms, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn render(&self, name: &str, help: &str, out: &mut String) {
+        out.push_str(&format!("# HELP {name} {help}\n# TYPE {name} histogram\n"));
+        let mut cumulative;
+        for (i, le) in LATENCY_BUCKETS_MS.iter().enumerate() {
+            cumulative = self.buckets[i].load(Ordering::Relaxed);
+            out.push_str(&format!(
+                "{name}_bucket{{le=\"{}\"}} {}\n",
+                *le as f64 / 1000.0,
+                cumulative
+            ));
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        out.push_str(&format!("{name}_bucket{{le=\"+Inf\"}} {count}\n"));
+        out.push_str(&format!(
+            "{name}_sum {}\n",
+            self.sum_ms.load(Ordering::Relaxed) as f64 / 1000.0
+        ));
+        out.push_str(&format!("{name}_count {count}\n"));
+    }
+}
 
-    for mut request in server.incoming_requests() {
-        let method = request.method().clone();
-        let url = request.url().to_string();
+/// Lightweight in-process metrics recorder scraped at `/automation/metrics`.
+struct Metrics {
+    requests: Mutex<Option<HashMap<String, u64>>>,
+    statuses: Mutex<Option<HashMap<u16, u64>>>,
+    command_latency: Histogram,
+    screenshot_duration: Histogram,
+}
+
+static METRICS: Metrics = Metrics {
+    requests: Mutex::new(None),
+    statuses: Mutex::new(None),
+    command_latency: Histogram::new(),
+    screenshot_duration: Histogram::new(),
+};
 
-        println!("[Automation] {} {}", method, url);
+/// Normalize a request target to one of the known route labels, bucketing
+/// everything else under `__other__`. This bounds metric label cardinality so
+/// arbitrary attacker-chosen URLs cannot grow `METRICS.requests` without bound.
+fn normalized_route(route: &str) -> &'static str {
+    match route {
+        "/automation/health" => "/automation/health",
+        "/automation/metrics" => "/automation/metrics",
+        "/automation/execute" => "/automation/execute",
+        "/automation/screenshot" => "/automation/screenshot",
+        "/automation/actions" => "/automation/actions",
+        "/automation/ws" => "/automation/ws",
+        "/automation/_result" => "/automation/_result",
+        "/automation/_event" => "/automation/_event",
+        _ => "__other__",
+    }
+}
 
-        let response = match (&method, url.as_str()) {
-            // Health check
-            (&Method::Get, "/automation/health") => {
-                json_response(serde_json::json!({
-                    "status": "ok",
-                    "port": PORT,
-                    "version": "1.0.0"
-                }))
+/// Record a handled request: one increment per route and per response status.
+fn record_request(route: &str, status: u16) {
+    let route = normalized_route(route);
+    if let Ok(mut guard) = METRICS.requests.lock() {
+        *guard
+            .get_or_insert_with(HashMap::new)
+            .entry(route.to_string())
+            .or_insert(0) += 1;
+    }
+    if let Ok(mut guard) = METRICS.statuses.lock() {
+        *guard
+            .get_or_insert_with(HashMap::new)
+            .entry(status)
+            .or_insert(0) += 1;
+    }
+}
+
+/// Escape a Prometheus label value (`\`, `"`, and newlines) per the exposition
+/// format, so label contents can never produce malformed output.
+fn escape_label(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Render the recorder in Prometheus text exposition format.
+fn render_metrics() -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP automation_requests_total Total requests handled per route.\n");
+    out.push_str("# TYPE automation_requests_total counter\n");
+    if let Ok(guard) = METRICS.requests.lock() {
+        if let Some(map) = guard.as_ref() {
+            for (route, count) in map {
+                out.push_str(&format!(
+                    "automation_requests_total{{route=\"{}\"}} {count}\n",
+                    escape_label(route)
+                ));
             }
+        }
+    }
 
-            // Execute command
-            (&Method::Post, "/automation/execute") => {
-                handle_execute(&app_handle, &mut request)
+    out.push_str("# HELP automation_responses_total Responses handled per status code.\n");
+    out.push_str("# TYPE automation_responses_total counter\n");
+    if let Ok(guard) = METRICS.statuses.lock() {
+        if let Some(map) = guard.as_ref() {
+            for (status, count) in map {
+                out.push_str(&format!("automation_responses_total{{status=\"{status}\"}} {count}\n"));
             }
+        }
+    }
 
-            // Screenshot
-            (&Method::Get, "/automation/screenshot") => {
-                handle_screenshot(&app_handle)
+    METRICS.command_latency.render(
+        "automation_command_duration_seconds",
+        "Command execution latency in seconds.",
+        &mut out,
+    );
+    METRICS.screenshot_duration.render(
+        "automation_screenshot_duration_seconds",
+        "Screenshot capture duration in seconds.",
+        &mut out,
+    );
+
+    out
+}
+
+/// A single upgraded WebSocket connection, used as a write-only push channel.
+type WsClient = Arc<Mutex<WebSocket<Box<dyn ReadWrite + Send>>>>;
+
+// Connected automation WebSocket clients. Frames produced anywhere in the
+// server (console output, command results, Tauri events) are fanned out here.
+static WS_CLIENTS: Mutex<Vec<WsClient>> = Mutex::new(Vec::new());
+
+/// Broadcast a JSON frame to every connected WebSocket client, dropping any
+/// connection that fails to receive it.
+pub fn broadcast(frame: serde_json::Value) {
+    let text = match serde_json::to_string(&frame) {
+        Ok(t) => t,
+        Err(_) => return,
+    };
+    let clients = match WS_CLIENTS.lock() {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+    if clients.is_empty() {
+        return;
+    }
+    let mut dead = Vec::new();
+    for (i, client) in clients.iter().enumerate() {
+        let ok = client
+            .lock()
+            .map(|mut ws| ws.send(Message::Text(text.clone())).is_ok())
+            .unwrap_or(false);
+        if !ok {
+            dead.push(i);
+        }
+    }
+    drop(clients);
+    if !dead.is_empty() {
+        if let Ok(mut clients) = WS_CLIENTS.lock() {
+            // Remove highest indices first so earlier indices stay valid.
+            for i in dead.into_iter().rev() {
+                if i < clients.len() {
+                    clients.remove(i);
+                }
             }
+        }
+    }
+}
+
+/// JS injected into every webview to forward `console.log`/`console.error` to
+/// the automation server, where it is streamed to WebSocket clients.
+pub fn console_shim_js() -> String {
+    let port = active_port();
+    let auth = internal_token();
+    format!(
+        r#"
+        (function() {{
+            if (window.__AUTOMATION_CONSOLE_SHIM__) return;
+            window.__AUTOMATION_CONSOLE_SHIM__ = true;
+            const __auth = '{auth}';
+            const forward = (level, args) => {{
+                try {{
+                    const message = args.map((a) => {{
+                        try {{ return typeof a === 'string' ? a : JSON.stringify(a); }}
+                        catch (e) {{ return String(a); }}
+                    }}).join(' ');
+                    fetch('http://127.0.0.1:{port}/automation/_event', {{
+                        method: 'POST',
+                        headers: {{ 'Content-Type': 'application/json' }},
+                        body: JSON.stringify({{ _auth: __auth, type: 'console', level: level, message: message }}),
+                    }}).catch(() => {{}});
+                }} catch (e) {{}}
+            }};
+            for (const level of ['log', 'info', 'warn', 'error']) {{
+                const original = console[level].bind(console);
+                console[level] = (...args) => {{ forward(level, args); original(...args); }};
+            }}
+        }})();
+        "#
+    )
+}
+
+/// How long `handle_execute` / `handle_screenshot` wait for the webview to post
+/// a result back before giving up.
+const DEFAULT_TIMEOUT_MS: u64 = 5000;
 
-            // CORS preflight
-            (&Method::Options, _) => {
-                cors_response()
+// Pending results keyed by correlation ID, populated by the webview via
+// `/automation/_result` and drained by the waiting request thread. Replaces the
+// old single screenshot buffer and the race-prone `thread::sleep` polling.
+/// Result channel state: the set of IDs a thread is currently waiting on, and
+/// any results delivered for them. Keeping both under one lock lets us drop
+/// results whose waiter has already given up, so nothing leaks.
+#[derive(Default)]
+struct ResultChannel {
+    waiting: std::collections::HashSet<String>,
+    results: HashMap<String, serde_json::Value>,
+}
+
+static RESULTS: Mutex<Option<ResultChannel>> = Mutex::new(None);
+static RESULTS_READY: Condvar = Condvar::new();
+static REQUEST_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Allocate a fresh, unguessable correlation ID for a round-trip into the
+/// webview. The random component means a foreign local process cannot forge a
+/// result by guessing the sequential counter.
+fn next_request_id() -> String {
+    use std::hash::{BuildHasher, Hasher};
+    let seq = REQUEST_SEQ.fetch_add(1, Ordering::Relaxed);
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(seq);
+    format!("req-{:016x}", hasher.finish())
+}
+
+/// Store a result posted back by the webview and wake the waiting thread.
+pub fn store_result(id: String, value: serde_json::Value) {
+    // Mirror the completion onto the WebSocket stream for reactive clients.
+    broadcast(serde_json::json!({ "type": "result", "id": id, "result": value }));
+    if let Ok(mut guard) = RESULTS.lock() {
+        let channel = guard.get_or_insert_with(ResultChannel::default);
+        // Only retain results for which a waiter is still registered; a result
+        // arriving after its waiter timed out is dropped rather than leaked.
+        if channel.waiting.contains(&id) {
+            channel.results.insert(id, value);
+            RESULTS_READY.notify_all();
+        }
+    }
+}
+
+/// Block until a result for `id` arrives or `timeout` elapses, tracking the
+/// remaining time across spurious wake-ups so the deadline is honored.
+fn wait_result(id: &str, timeout: Duration) -> Option<serde_json::Value> {
+    let deadline = std::time::Instant::now() + timeout;
+    let mut guard = RESULTS.lock().ok()?;
+    guard
+        .get_or_insert_with(ResultChannel::default)
+        .waiting
+        .insert(id.to_string());
+
+    let result = loop {
+        if let Some(channel) = guard.as_mut() {
+            if let Some(value) = channel.results.remove(id) {
+                break Some(value);
             }
+        }
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        if remaining.is_zero() {
+            break None;
+        }
+        let (g, _) = RESULTS_READY.wait_timeout(guard, remaining).ok()?;
+        guard = g;
+    };
 
-            // 404
-            _ => {
-                json_response_with_status(
-                    serde_json::json!({ "error": "Not found" }),
-                    404
-                )
+    // Deregister and discard any late-arriving result for this ID.
+    if let Some(channel) = guard.as_mut() {
+        channel.waiting.remove(id);
+        channel.results.remove(id);
+    }
+    result
+}
+
+/// Start the HTTP server
+pub fn start_server(app_handle: AppHandle<tauri::Wry>, config: ServerConfig) {
+    let addr = format!("{}:{}", config.host, config.port);
+    set_active_port(config.port);
+
+    let result = match config.tls {
+        Some(tls) => {
+            let ssl = tiny_http::SslConfig {
+                certificate: tls.certificate,
+                private_key: tls.private_key,
+            };
+            Server::https(&addr, ssl)
+        }
+        None => Server::http(&addr),
+    };
+
+    let server = match result {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::error!(%addr, error = %e, "failed to start automation server");
+            return;
+        }
+    };
+
+    tracing::info!(%addr, "automation server listening");
+
+    // Dispatch each request on its own thread. Handlers like `handle_execute`
+    // block in `wait_result` for the webview to `POST /automation/_result`, so
+    // the accept loop must never be the thing that dequeues that post. A fixed
+    // worker pool would merely raise the deadlock threshold: N concurrent
+    // blocking commands would occupy all N workers and starve result delivery.
+    // Spawning per request keeps an unbounded path open for `_result`/`_event`.
+    for request in server.incoming_requests() {
+        let app_handle = app_handle.clone();
+        std::thread::spawn(move || handle_request(&app_handle, request));
+    }
+}
+
+/// Route and respond to a single request. Runs on a worker thread so blocking
+/// handlers never stall the accept loop.
+fn handle_request(app_handle: &AppHandle<tauri::Wry>, mut request: tiny_http::Request) {
+    let method = request.method().clone();
+    let url = request.url().to_string();
+    let route = url.split('?').next().unwrap_or(&url).to_string();
+    let span = tracing::info_span!("request", method = %method, route = %route);
+    let _enter = span.enter();
+    let started = std::time::Instant::now();
+
+    // WebSocket upgrade takes over the connection and never produces a
+    // normal response, so handle it before the response-returning router. It
+    // streams command results and console output, so it must carry the same
+    // bearer token — supplied via query param or `Sec-WebSocket-Protocol`,
+    // since browsers can't set arbitrary WS request headers.
+    if method == Method::Get && route == "/automation/ws" {
+        if !is_ws_authorized(app_handle, &request, &url) {
+            record_request(&route, 401);
+            let response = json_response_with_status(
+                serde_json::json!({ "error": "Unauthorized" }),
+                401,
+            );
+            if let Err(e) = request.respond(response) {
+                tracing::error!(error = %e, "failed to send response");
             }
-        };
+            return;
+        }
+        record_request(&route, 101);
+        drop(_enter);
+        handle_ws_upgrade(request);
+        return;
+    }
 
+    // Gate every script-evaluating route behind the bearer token before any
+    // script runs. `_result`/`_event` are instead restricted to the webview by
+    // the internal secret it echoes back (see `take_internal_auth`), which a
+    // foreign loopback process does not hold.
+    let protected = matches!(
+        (&method, route.as_str()),
+        (&Method::Post, "/automation/execute")
+            | (&Method::Post, "/automation/actions")
+            | (&Method::Get, "/automation/screenshot")
+    );
+    if protected && !is_authorized(app_handle, &request) {
+        let response = json_response_with_status(
+            serde_json::json!({ "error": "Unauthorized" }),
+            401,
+        );
+        record_request(&route, 401);
         if let Err(e) = request.respond(response) {
-            eprintln!("[Automation] Failed to send response: {}", e);
+            tracing::error!(error = %e, "failed to send response");
         }
+        return;
+    }
+
+    let response = match (&method, url.as_str()) {
+        // Health check
+        (&Method::Get, "/automation/health") => {
+            json_response(serde_json::json!({
+                "status": "ok",
+                "port": active_port(),
+                "version": "1.0.0"
+            }))
+        }
+
+        // Prometheus metrics
+        (&Method::Get, "/automation/metrics") => {
+            text_response(render_metrics())
+        }
+
+        // Execute command
+        (&Method::Post, "/automation/execute") => {
+            let response = handle_execute(&app_handle, &mut request);
+            METRICS.command_latency.observe(started.elapsed().as_millis() as u64);
+            response
+        }
+
+        // Screenshot
+        (&Method::Get, "/automation/screenshot") => {
+            let response = handle_screenshot(&app_handle);
+            METRICS.screenshot_duration.observe(started.elapsed().as_millis() as u64);
+            response
+        }
+
+        // W3C WebDriver Actions
+        (&Method::Post, "/automation/actions") => {
+            handle_actions(&app_handle, &mut request)
+        }
+
+        // Internal: webview posts command/screenshot results here
+        (&Method::Post, "/automation/_result") => {
+            handle_result(&mut request)
+        }
+
+        // Internal: webview forwards console output / events here
+        (&Method::Post, "/automation/_event") => {
+            handle_event(&mut request)
+        }
+
+        // CORS preflight
+        (&Method::Options, _) => {
+            cors_response()
+        }
+
+        // 404
+        _ => {
+            json_response_with_status(
+                serde_json::json!({ "error": "Not found" }),
+                404
+            )
+        }
+    };
+
+    let status = response.status_code().0;
+    record_request(&route, status);
+    tracing::info!(status, elapsed_ms = started.elapsed().as_millis() as u64, "handled");
+
+    if let Err(e) = request.respond(response) {
+        tracing::error!(error = %e, "failed to send response");
     }
 }
 
@@ -89,6 +559,8 @@ fn handle_execute(
     app_handle: &AppHandle<tauri::Wry>,
     request: &mut tiny_http::Request,
 ) -> Response<std::io::Cursor<Vec<u8>>> {
+    let state = app_handle.state::<crate::AutomationState>();
+
     // Read body
     let mut body = String::new();
     if let Err(e) = request.as_reader().read_to_string(&mut body) {
@@ -119,7 +591,19 @@ fn handle_execute(
         }
     };
 
+    // Enforce the command scope (forbid always wins).
+    if !state.scope.is_allowed(&command) {
+        return json_response_with_status(
+            serde_json::json!({ "error": format!("Command '{}' not permitted by scope", command) }),
+            403
+        );
+    }
+
     let args = payload.get("args").cloned().unwrap_or(serde_json::json!({}));
+    let timeout = payload
+        .get("timeout_ms")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_TIMEOUT_MS);
 
     // Get the main window
     let window = match app_handle.get_window("main") {
@@ -132,25 +616,33 @@ fn handle_execute(
         }
     };
 
-    // Build JavaScript to execute
+    // Allocate a correlation ID and inject it so the webview can post the real
+    // result back to `/automation/_result` when the command settles.
+    let id = next_request_id();
+    let port = active_port();
+    let auth = internal_token();
     let args_json = serde_json::to_string(&args).unwrap_or_else(|_| "{}".to_string());
     let script = format!(
         r#"
         (async function() {{
+            const __id = '{id}';
+            const __post = (payload) => fetch('http://127.0.0.1:{port}/automation/_result', {{
+                method: 'POST',
+                headers: {{ 'Content-Type': 'application/json' }},
+                body: JSON.stringify(Object.assign({{ _auth: '{auth}', id: __id }}, payload)),
+            }}).catch(() => {{}});
             if (typeof window.__TAURI_AUTOMATION__ === 'undefined') {{
-                console.error('[Automation] Not initialized');
+                __post({{ success: false, error: 'Automation not initialized' }});
                 return;
             }}
             try {{
-                const result = await window.__TAURI_AUTOMATION__.execute('{}', {});
-                window.__TAURI_AUTOMATION__._lastResult = {{ success: true, result: result }};
+                const result = await window.__TAURI_AUTOMATION__.execute('{command}', {args_json});
+                __post({{ success: true, result: result }});
             }} catch (e) {{
-                window.__TAURI_AUTOMATION__._lastResult = {{ success: false, error: e.message || String(e) }};
+                __post({{ success: false, error: e.message || String(e) }});
             }}
         }})();
-        "#,
-        command,
-        args_json
+        "#
     );
 
     // Execute the script
@@ -161,15 +653,494 @@ fn handle_execute(
         );
     }
 
-    // Wait a bit for async commands to complete
-    std::thread::sleep(std::time::Duration::from_millis(100));
+    // Block until the webview posts the genuine result (or we time out).
+    match wait_result(&id, Duration::from_millis(timeout)) {
+        Some(result) => {
+            let failed = result.get("success").and_then(|v| v.as_bool()) == Some(false);
+            if failed {
+                json_response_with_status(result, 500)
+            } else {
+                json_response(result)
+            }
+        }
+        None => json_response_with_status(
+            serde_json::json!({ "success": false, "error": "Command timed out", "command": command }),
+            504
+        ),
+    }
+}
+
+/// Handle a result posted back by the webview, keyed by correlation ID.
+fn handle_result(request: &mut tiny_http::Request) -> Response<std::io::Cursor<Vec<u8>>> {
+    let mut body = String::new();
+    if let Err(e) = request.as_reader().read_to_string(&mut body) {
+        return json_response_with_status(
+            serde_json::json!({ "error": format!("Failed to read body: {}", e) }),
+            400
+        );
+    }
+
+    let mut payload: serde_json::Value = match serde_json::from_str(&body) {
+        Ok(v) => v,
+        Err(e) => {
+            return json_response_with_status(
+                serde_json::json!({ "error": format!("Invalid JSON: {}", e) }),
+                400
+            );
+        }
+    };
+
+    if !take_internal_auth(&mut payload) {
+        return json_response_with_status(
+            serde_json::json!({ "error": "Unauthorized" }),
+            403
+        );
+    }
+
+    let id = match payload.get("id").and_then(|v| v.as_str()) {
+        Some(id) => id.to_string(),
+        None => {
+            return json_response_with_status(
+                serde_json::json!({ "error": "Missing 'id' field" }),
+                400
+            );
+        }
+    };
+
+    if let Some(obj) = payload.as_object_mut() {
+        obj.remove("id");
+    }
+    store_result(id, payload);
+
+    json_response(serde_json::json!({ "success": true }))
+}
+
+/// Verify and strip the internal `_auth` secret injected into webview scripts,
+/// so only the app's own webview (not another loopback process) can post to the
+/// internal `_result`/`_event` endpoints.
+fn take_internal_auth(payload: &mut serde_json::Value) -> bool {
+    let presented = payload
+        .as_object_mut()
+        .and_then(|obj| obj.remove("_auth"));
+    match presented.as_ref().and_then(|v| v.as_str()) {
+        Some(token) => token == internal_token(),
+        None => false,
+    }
+}
+
+/// Handle a console/event frame forwarded by the webview shim and fan it out to
+/// connected WebSocket clients.
+fn handle_event(request: &mut tiny_http::Request) -> Response<std::io::Cursor<Vec<u8>>> {
+    let mut body = String::new();
+    if let Err(e) = request.as_reader().read_to_string(&mut body) {
+        return json_response_with_status(
+            serde_json::json!({ "error": format!("Failed to read body: {}", e) }),
+            400
+        );
+    }
+
+    let mut frame: serde_json::Value = match serde_json::from_str(&body) {
+        Ok(v) => v,
+        Err(e) => {
+            return json_response_with_status(
+                serde_json::json!({ "error": format!("Invalid JSON: {}", e) }),
+                400
+            );
+        }
+    };
+
+    if !take_internal_auth(&mut frame) {
+        return json_response_with_status(
+            serde_json::json!({ "error": "Unauthorized" }),
+            403
+        );
+    }
+
+    broadcast(frame);
+    json_response(serde_json::json!({ "success": true }))
+}
+
+/// Upgrade a request to a WebSocket connection and register it for fan-out.
+///
+/// tiny_http has already parsed the request line and headers, so we complete
+/// the handshake manually (`Sec-WebSocket-Accept`) and wrap the returned raw
+/// socket as a server-role `tungstenite` WebSocket.
+fn handle_ws_upgrade(request: tiny_http::Request) {
+    let key = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Sec-WebSocket-Key"))
+        .map(|h| h.value.as_str().to_string());
+
+    let key = match key {
+        Some(k) => k,
+        None => {
+            let _ = request.respond(json_response_with_status(
+                serde_json::json!({ "error": "Missing Sec-WebSocket-Key" }),
+                400,
+            ));
+            return;
+        }
+    };
+
+    let accept = tungstenite::handshake::derive_accept_key(key.as_bytes());
+    let mut response = Response::new_empty(tiny_http::StatusCode(101))
+        .with_header(Header::from_bytes(&b"Upgrade"[..], &b"websocket"[..]).unwrap())
+        .with_header(Header::from_bytes(&b"Connection"[..], &b"Upgrade"[..]).unwrap())
+        .with_header(Header::from_bytes(&b"Sec-WebSocket-Accept"[..], accept.as_bytes()).unwrap());
+
+    // A client that offered subprotocols (e.g. the token, see
+    // `is_ws_authorized`) aborts the handshake unless one is echoed back.
+    let protocol = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Sec-WebSocket-Protocol"))
+        .and_then(|h| h.value.as_str().split(',').next().map(|p| p.trim().to_string()));
+    if let Some(protocol) = protocol {
+        if let Ok(header) = Header::from_bytes(&b"Sec-WebSocket-Protocol"[..], protocol.as_bytes()) {
+            response = response.with_header(header);
+        }
+    }
+
+    let stream = request.upgrade("websocket", response);
+    let ws = WebSocket::from_raw_socket(stream, Role::Server, None);
+    let client: WsClient = Arc::new(Mutex::new(ws));
+
+    if let Ok(mut clients) = WS_CLIENTS.lock() {
+        clients.push(Arc::clone(&client));
+    }
+    tracing::info!("websocket client connected");
+
+    // This is a server push channel: we only ever write to the socket. We
+    // deliberately do not spawn a reader that blocks on `ws.read()`, because
+    // holding the client `Mutex` across that blocking call would stall every
+    // `broadcast` for the idle lifetime of the connection. A disconnected peer
+    // is detected and pruned the next time `broadcast` fails to write to it.
+}
+
+/// Handle a W3C WebDriver "Actions" request.
+///
+/// The payload is `{ "actions": [ <input source>, ... ] }`, where each input
+/// source carries an `id`, a `type` (`pointer`, `key`, `wheel`, or `none`),
+/// optional `parameters`, and an `actions` array. Sources are executed in
+/// lockstep by *tick*: at tick `i` we collect the `i`-th action from every
+/// source and translate each into the matching DOM event. A tick lasts the
+/// maximum of its actions' durations; it is split into sub-steps (see
+/// [`build_tick_scripts`]) so interpolated pointer moves are dispatched across
+/// that duration rather than all at once.
+fn handle_actions(
+    app_handle: &AppHandle<tauri::Wry>,
+    request: &mut tiny_http::Request,
+) -> Response<std::io::Cursor<Vec<u8>>> {
+    // Read body
+    let mut body = String::new();
+    if let Err(e) = request.as_reader().read_to_string(&mut body) {
+        return json_response_with_status(
+            serde_json::json!({ "error": format!("Failed to read body: {}", e) }),
+            400
+        );
+    }
+
+    // Parse JSON
+    let payload: serde_json::Value = match serde_json::from_str(&body) {
+        Ok(v) => v,
+        Err(e) => {
+            return json_response_with_status(
+                serde_json::json!({ "error": format!("Invalid JSON: {}", e) }),
+                400
+            );
+        }
+    };
+
+    let sources = match payload.get("actions").and_then(|v| v.as_array()) {
+        Some(s) => s.clone(),
+        None => {
+            return json_response_with_status(
+                serde_json::json!({ "error": "Missing 'actions' array" }),
+                400
+            );
+        }
+    };
+
+    // Get the main window
+    let window = match app_handle.get_window("main") {
+        Some(w) => w,
+        None => {
+            return json_response_with_status(
+                serde_json::json!({ "error": "Main window not found" }),
+                500
+            );
+        }
+    };
+
+    let ticks = match build_tick_scripts(&sources) {
+        Ok(t) => t,
+        Err(e) => {
+            return json_response_with_status(
+                serde_json::json!({ "error": e }),
+                400
+            );
+        }
+    };
+
+    for (script, duration_ms) in ticks {
+        if !script.is_empty() {
+            if let Err(e) = window.eval(&script) {
+                return json_response_with_status(
+                    serde_json::json!({ "error": format!("Action dispatch failed: {}", e) }),
+                    500
+                );
+            }
+        }
+        if duration_ms > 0 {
+            std::thread::sleep(std::time::Duration::from_millis(duration_ms));
+        }
+    }
+
+    json_response(serde_json::json!({ "success": true }))
+}
 
-    // Return success - the result is stored in the webview for debugging
-    json_response(serde_json::json!({
-        "success": true,
-        "message": "Command executed",
-        "command": command
-    }))
+/// Per-source state tracked across ticks while synthesizing input.
+#[derive(Default, Clone)]
+struct SourceState {
+    x: f64,
+    y: f64,
+    buttons: u32,
+    pointer_type: String,
+}
+
+/// Translate the parsed input sources into a flat list of `(script, sleep_ms)`
+/// sub-steps. Each tick is subdivided so interpolated `pointerMove`s are spread
+/// *over* the tick's duration — the sleep between sub-steps gives listeners a
+/// timed path rather than a burst of moves followed by a dead pause. Every
+/// sub-step dispatches all sources' events for that instant together, so
+/// chorded / multi-finger sequences stay synchronized.
+fn build_tick_scripts(sources: &[serde_json::Value]) -> Result<Vec<(String, u64)>, String> {
+    // Resolve per-source metadata and the number of ticks.
+    let mut states: Vec<SourceState> = Vec::with_capacity(sources.len());
+    let mut tick_count = 0usize;
+
+    for source in sources {
+        let pointer_type = source
+            .get("parameters")
+            .and_then(|p| p.get("pointerType"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("mouse")
+            .to_string();
+        states.push(SourceState {
+            pointer_type,
+            ..Default::default()
+        });
+        let len = source
+            .get("actions")
+            .and_then(|v| v.as_array())
+            .map(|a| a.len())
+            .unwrap_or(0);
+        tick_count = tick_count.max(len);
+    }
+
+    // Active modifier code points, shared across key sources (chords).
+    let mut modifiers: std::collections::BTreeSet<String> = std::collections::BTreeSet::new();
+
+    let mut steps_out: Vec<(String, u64)> = Vec::new();
+
+    for i in 0..tick_count {
+        // The tick's duration is the max of its actions' durations; it bounds
+        // how long the interpolated moves are spread across.
+        let mut tick_duration = 0u64;
+        for source in sources {
+            if let Some(action) = source
+                .get("actions")
+                .and_then(|v| v.as_array())
+                .and_then(|a| a.get(i))
+            {
+                let duration = action.get("duration").and_then(|v| v.as_u64()).unwrap_or(0);
+                tick_duration = tick_duration.max(duration);
+            }
+        }
+
+        // Subdivide the tick into sub-steps (~60fps), at least one.
+        let sub_steps = ((tick_duration / 16).max(1)).min(60) as usize;
+        let mut stmts: Vec<Vec<String>> = vec![Vec::new(); sub_steps];
+
+        for (source, state) in sources.iter().zip(states.iter_mut()) {
+            let source_type = source.get("type").and_then(|v| v.as_str()).unwrap_or("none");
+            let action = source
+                .get("actions")
+                .and_then(|v| v.as_array())
+                .and_then(|a| a.get(i));
+            let action = match action {
+                Some(a) => a,
+                None => continue,
+            };
+
+            let sub = action.get("type").and_then(|v| v.as_str()).unwrap_or("pause");
+
+            match (source_type, sub) {
+                (_, "pause") => {}
+                ("pointer", "pointerMove") => {
+                    let target_x = action.get("x").and_then(|v| v.as_f64()).unwrap_or(0.0);
+                    let target_y = action.get("y").and_then(|v| v.as_f64()).unwrap_or(0.0);
+                    // `origin` is `viewport`, `pointer`, or an element handle.
+                    // Element handles would need a client-side rect lookup we
+                    // don't maintain, so reject them rather than teleport to 0,0.
+                    let (base_x, base_y) = match action.get("origin") {
+                        None => (0.0, 0.0),
+                        Some(o) => match o.as_str() {
+                            Some("viewport") => (0.0, 0.0),
+                            Some("pointer") => (state.x, state.y),
+                            _ => {
+                                return Err(
+                                    "Unsupported pointerMove origin (element handles are not supported)".to_string(),
+                                );
+                            }
+                        },
+                    };
+                    let dest_x = base_x + target_x;
+                    let dest_y = base_y + target_y;
+
+                    // Interpolate one move per sub-step so the path is spread
+                    // across the tick duration by the inter-step sleeps.
+                    let (from_x, from_y) = (state.x, state.y);
+                    for (j, slot) in stmts.iter_mut().enumerate() {
+                        let t = (j + 1) as f64 / sub_steps as f64;
+                        let ix = from_x + (dest_x - from_x) * t;
+                        let iy = from_y + (dest_y - from_y) * t;
+                        slot.push(pointer_event_stmt(
+                            "mousemove", ix, iy, state.buttons, &state.pointer_type, None,
+                        ));
+                    }
+                    state.x = dest_x;
+                    state.y = dest_y;
+                }
+                ("pointer", "pointerDown") => {
+                    let button = action.get("button").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+                    state.buttons |= 1 << button;
+                    stmts[0].push(pointer_event_stmt(
+                        "mousedown", state.x, state.y, state.buttons, &state.pointer_type, Some(button),
+                    ));
+                }
+                ("pointer", "pointerUp") => {
+                    let button = action.get("button").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+                    state.buttons &= !(1 << button);
+                    stmts[0].push(pointer_event_stmt(
+                        "mouseup", state.x, state.y, state.buttons, &state.pointer_type, Some(button),
+                    ));
+                }
+                ("key", "keyDown") => {
+                    let value = action.get("value").and_then(|v| v.as_str()).unwrap_or("");
+                    modifiers.insert(value.to_string());
+                    stmts[0].push(key_event_stmt("keydown", value, &modifiers));
+                }
+                ("key", "keyUp") => {
+                    let value = action.get("value").and_then(|v| v.as_str()).unwrap_or("");
+                    modifiers.remove(value);
+                    stmts[0].push(key_event_stmt("keyup", value, &modifiers));
+                }
+                ("wheel", "scroll") => {
+                    let x = action.get("x").and_then(|v| v.as_f64()).unwrap_or(state.x);
+                    let y = action.get("y").and_then(|v| v.as_f64()).unwrap_or(state.y);
+                    let dx = action.get("deltaX").and_then(|v| v.as_f64()).unwrap_or(0.0);
+                    let dy = action.get("deltaY").and_then(|v| v.as_f64()).unwrap_or(0.0);
+                    stmts[0].push(wheel_event_stmt(x, y, dx, dy));
+                }
+                _ => {
+                    return Err(format!(
+                        "Unsupported action '{}' for source type '{}'",
+                        sub, source_type
+                    ));
+                }
+            }
+        }
+
+        // Emit each sub-step with its share of the tick duration so the sleeps
+        // are interleaved between the interpolated moves. The final sub-step
+        // carries any rounding remainder.
+        let per_step = tick_duration / sub_steps as u64;
+        for (j, slot) in stmts.into_iter().enumerate() {
+            let script = if slot.is_empty() {
+                String::new()
+            } else {
+                format!("(function() {{ {} }})();", slot.join(" "))
+            };
+            let sleep = if j + 1 == sub_steps {
+                tick_duration - per_step * (sub_steps as u64 - 1)
+            } else {
+                per_step
+            };
+            steps_out.push((script, sleep));
+        }
+    }
+
+    Ok(steps_out)
+}
+
+/// JS that dispatches a pointer-derived mouse event at `(x, y)`.
+fn pointer_event_stmt(
+    event: &str,
+    x: f64,
+    y: f64,
+    buttons: u32,
+    pointer_type: &str,
+    button: Option<u32>,
+) -> String {
+    let button = button.unwrap_or(0);
+    format!(
+        "{{ const el = document.elementFromPoint({x}, {y}) || document.documentElement; \
+el.dispatchEvent(new MouseEvent('{event}', {{ bubbles: true, cancelable: true, view: window, \
+clientX: {x}, clientY: {y}, button: {button}, buttons: {buttons} }})); \
+void '{pointer_type}'; }}"
+    )
+}
+
+/// JS that dispatches a keyboard event carrying the active modifier state.
+fn key_event_stmt(
+    event: &str,
+    value: &str,
+    modifiers: &std::collections::BTreeSet<String>,
+) -> String {
+    let key = js_string(value);
+    let ctrl = modifiers.contains("\u{E009}") || modifiers.contains("\u{E051}");
+    let shift = modifiers.contains("\u{E008}") || modifiers.contains("\u{E050}");
+    let alt = modifiers.contains("\u{E00A}") || modifiers.contains("\u{E052}");
+    let meta = modifiers.contains("\u{E03D}") || modifiers.contains("\u{E053}");
+    format!(
+        "{{ const t = document.activeElement || document.body; \
+t.dispatchEvent(new KeyboardEvent('{event}', {{ bubbles: true, cancelable: true, \
+key: {key}, ctrlKey: {ctrl}, shiftKey: {shift}, altKey: {alt}, metaKey: {meta} }})); }}"
+    )
+}
+
+/// JS that dispatches a wheel event at `(x, y)`.
+fn wheel_event_stmt(x: f64, y: f64, dx: f64, dy: f64) -> String {
+    format!(
+        "{{ const el = document.elementFromPoint({x}, {y}) || document.documentElement; \
+el.dispatchEvent(new WheelEvent('wheel', {{ bubbles: true, cancelable: true, view: window, \
+clientX: {x}, clientY: {y}, deltaX: {dx}, deltaY: {dy} }})); }}"
+    )
+}
+
+/// Escape a string into a JS string literal (used for key values).
+fn js_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            _ => {
+                if (c as u32) < 0x20 || (c as u32) >= 0x7f {
+                    out.push_str(&format!("\\u{:04x}", c as u32));
+                } else {
+                    out.push(c);
+                }
+            }
+        }
+    }
+    out.push('"');
+    out
 }
 
 /// Handle screenshot request
@@ -184,54 +1155,76 @@ fn handle_screenshot(app_handle: &AppHandle<tauri::Wry>) -> Response<std::io::Cu
         }
     };
 
-    // Request screenshot from JS
-    let script = r#"
-        (async function() {
-            if (typeof window.__TAURI_AUTOMATION__ === 'undefined') {
-                console.error('[Automation] Not initialized');
+    // Request a screenshot from JS, tagged with a correlation ID so the webview
+    // posts the captured data URL back to `/automation/_result`.
+    let id = next_request_id();
+    let port = active_port();
+    let auth = internal_token();
+    let script = format!(
+        r#"
+        (async function() {{
+            const __id = '{id}';
+            const __post = (payload) => fetch('http://127.0.0.1:{port}/automation/_result', {{
+                method: 'POST',
+                headers: {{ 'Content-Type': 'application/json' }},
+                body: JSON.stringify(Object.assign({{ _auth: '{auth}', id: __id }}, payload)),
+            }}).catch(() => {{}});
+            if (typeof window.__TAURI_AUTOMATION__ === 'undefined') {{
+                __post({{ error: 'Automation not initialized' }});
                 return;
-            }
-            try {
-                await window.__TAURI_AUTOMATION__.captureAndSend();
-            } catch (e) {
-                console.error('[Automation] Screenshot failed:', e);
-            }
-        })();
-    "#;
+            }}
+            try {{
+                const dataUrl = await window.__TAURI_AUTOMATION__.captureAndSend();
+                __post({{ dataUrl: dataUrl }});
+            }} catch (e) {{
+                __post({{ error: e.message || String(e) }});
+            }}
+        }})();
+        "#
+    );
 
-    if let Err(e) = window.eval(script) {
+    if let Err(e) = window.eval(&script) {
         return json_response_with_status(
             serde_json::json!({ "error": format!("Screenshot request failed: {}", e) }),
             500
         );
     }
 
-    // Wait for JS to send the screenshot data
-    // html2canvas can take a while, especially on first load
-    std::thread::sleep(std::time::Duration::from_millis(2000));
-
-    // Check if we have screenshot data
-    if let Some(data_url) = take_screenshot_data() {
-        // Parse data URL: data:image/png;base64,....
-        if let Some(base64_data) = data_url.strip_prefix("data:image/png;base64,") {
-            match base64_decode(base64_data) {
-                Ok(bytes) => {
-                    return png_response(bytes);
-                }
-                Err(e) => {
-                    return json_response_with_status(
-                        serde_json::json!({ "error": format!("Base64 decode failed: {}", e) }),
-                        500
-                    );
-                }
-            }
+    // Block until the webview posts the capture back (html2canvas can be slow,
+    // especially on first load).
+    let result = match wait_result(&id, Duration::from_millis(DEFAULT_TIMEOUT_MS)) {
+        Some(r) => r,
+        None => {
+            return json_response_with_status(
+                serde_json::json!({ "error": "Screenshot timed out. Make sure html2canvas is loaded." }),
+                504
+            );
         }
+    };
+
+    if let Some(err) = result.get("error").and_then(|v| v.as_str()) {
+        return json_response_with_status(
+            serde_json::json!({ "error": err }),
+            500
+        );
     }
 
-    json_response_with_status(
-        serde_json::json!({ "error": "Screenshot not available. Make sure html2canvas is loaded." }),
-        500
-    )
+    let data_url = result.get("dataUrl").and_then(|v| v.as_str()).unwrap_or("");
+    // Parse data URL: data:image/png;base64,....
+    if let Some(base64_data) = data_url.strip_prefix("data:image/png;base64,") {
+        match base64_decode(base64_data) {
+            Ok(bytes) => png_response(bytes),
+            Err(e) => json_response_with_status(
+                serde_json::json!({ "error": format!("Base64 decode failed: {}", e) }),
+                500
+            ),
+        }
+    } else {
+        json_response_with_status(
+            serde_json::json!({ "error": "Screenshot not available. Make sure html2canvas is loaded." }),
+            500
+        )
+    }
 }
 
 /// Simple base64 decoder
@@ -266,6 +1259,78 @@ fn base64_decode(input: &str) -> Result<Vec<u8>, String> {
     Ok(output)
 }
 
+/// Whether a request satisfies the configured bearer token. Returns `true` when
+/// no token is configured.
+fn is_authorized(app_handle: &AppHandle<tauri::Wry>, request: &tiny_http::Request) -> bool {
+    match app_handle.state::<crate::AutomationState>().token.as_deref() {
+        Some(expected) => bearer_token(request).as_deref() == Some(expected),
+        None => true,
+    }
+}
+
+/// Whether a WebSocket upgrade is authorized. Equivalent to [`is_authorized`]
+/// but also accepts the token via a `?token=` query parameter or the
+/// `Sec-WebSocket-Protocol` header, which browser WebSocket clients can set
+/// where arbitrary request headers are not available.
+fn is_ws_authorized(
+    app_handle: &AppHandle<tauri::Wry>,
+    request: &tiny_http::Request,
+    url: &str,
+) -> bool {
+    let expected = match app_handle.state::<crate::AutomationState>().token.as_deref() {
+        Some(t) => t.to_string(),
+        None => return true,
+    };
+
+    if bearer_token(request).as_deref() == Some(expected.as_str()) {
+        return true;
+    }
+
+    // `?token=<token>` query parameter.
+    if let Some(query) = url.split('?').nth(1) {
+        for pair in query.split('&') {
+            if let Some(value) = pair.strip_prefix("token=") {
+                if value == expected {
+                    return true;
+                }
+            }
+        }
+    }
+
+    // `Sec-WebSocket-Protocol: <token>` header (comma-separated list).
+    if let Some(header) = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Sec-WebSocket-Protocol"))
+    {
+        if header
+            .value
+            .as_str()
+            .split(',')
+            .any(|p| p.trim() == expected)
+        {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Extract the token from an `Authorization: Bearer <token>` header, if present.
+fn bearer_token(request: &tiny_http::Request) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .and_then(|h| {
+            let value = h.value.as_str();
+            value
+                .strip_prefix("Bearer ")
+                .or_else(|| value.strip_prefix("bearer "))
+                .map(|t| t.trim().to_string())
+        })
+}
+
 /// Create a JSON response
 fn json_response(data: serde_json::Value) -> Response<std::io::Cursor<Vec<u8>>> {
     json_response_with_status(data, 200)
@@ -283,7 +1348,28 @@ fn json_response_with_status(data: serde_json::Value, status: u16) -> Response<s
             Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap(),
             Header::from_bytes(&b"Access-Control-Allow-Origin"[..], &b"*"[..]).unwrap(),
             Header::from_bytes(&b"Access-Control-Allow-Methods"[..], &b"GET, POST, OPTIONS"[..]).unwrap(),
-            Header::from_bytes(&b"Access-Control-Allow-Headers"[..], &b"Content-Type"[..]).unwrap(),
+            Header::from_bytes(&b"Access-Control-Allow-Headers"[..], &b"Content-Type, Authorization"[..]).unwrap(),
+        ],
+        cursor,
+        Some(len),
+        None,
+    )
+}
+
+/// Create a Prometheus text-exposition response.
+fn text_response(body: String) -> Response<std::io::Cursor<Vec<u8>>> {
+    let bytes = body.into_bytes();
+    let len = bytes.len();
+    let cursor = std::io::Cursor::new(bytes);
+
+    Response::new(
+        tiny_http::StatusCode(200),
+        vec![
+            Header::from_bytes(
+                &b"Content-Type"[..],
+                &b"text/plain; version=0.0.4"[..],
+            ).unwrap(),
+            Header::from_bytes(&b"Access-Control-Allow-Origin"[..], &b"*"[..]).unwrap(),
         ],
         cursor,
         Some(len),
@@ -317,7 +1403,7 @@ fn cors_response() -> Response<std::io::Cursor<Vec<u8>>> {
         vec![
             Header::from_bytes(&b"Access-Control-Allow-Origin"[..], &b"*"[..]).unwrap(),
             Header::from_bytes(&b"Access-Control-Allow-Methods"[..], &b"GET, POST, OPTIONS"[..]).unwrap(),
-            Header::from_bytes(&b"Access-Control-Allow-Headers"[..], &b"Content-Type"[..]).unwrap(),
+            Header::from_bytes(&b"Access-Control-Allow-Headers"[..], &b"Content-Type, Authorization"[..]).unwrap(),
         ],
         cursor,
         Some(0),